@@ -0,0 +1,82 @@
+// Copyright 2022 Zinc Labs Inc. and Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::infra::errors::Result;
+
+/// per-column min/max value statistics for a single parquet file, written
+/// once at ingestion time and consulted by query-time file pruning
+/// (`storage::file_could_match`) so files whose label values can't possibly
+/// satisfy a matcher are skipped without ever being opened. This is read
+/// back from the same persisted file-list metadata record
+/// `db::file_list::get` looks up -- not a process-local cache -- because
+/// ingestion (which writes the stats) and the query node (which reads them
+/// here) are different processes; anything not persisted would simply never
+/// be visible on the query side.
+#[derive(Debug, Clone, Default)]
+pub struct FileStats {
+    /// column name -> (min, max), compared lexicographically since label
+    /// values are always strings
+    column_ranges: HashMap<String, (String, String)>,
+}
+
+impl FileStats {
+    /// the recorded `(min, max)` value range for `column`, if any stats were
+    /// collected for it at ingestion. `None` means "unknown", which callers
+    /// must treat as "could match" rather than "can't match".
+    pub fn column_range(&self, column: &str) -> Option<(&str, &str)> {
+        self.column_ranges
+            .get(column)
+            .map(|(min, max)| (min.as_str(), max.as_str()))
+    }
+}
+
+/// persist the per-column value ranges observed while writing `file` onto
+/// its file-list metadata record, so the very next query to touch the file
+/// -- on any query node -- can prune with them. Called by the ingestion
+/// writer once a parquet file has been flushed and its file-list entry
+/// written; this is a separate call rather than folding it into that write
+/// because the statistics are computed from the in-memory record batch
+/// while the file-list entry itself only needs the file's time range.
+pub async fn write_file_stats(
+    file: &str,
+    column_ranges: HashMap<String, (String, String)>,
+) -> Result<()> {
+    crate::service::db::file_list::set_column_stats(file, column_ranges).await
+}
+
+/// look up the persisted statistics for `file` from its file-list metadata
+/// record. Returns `Ok(None)` rather than an error when nothing was
+/// recorded, since an unindexed file (e.g. one written before this feature
+/// existed) is a normal, expected case and not a failure.
+pub async fn get_file_stats(file: &str) -> Result<Option<FileStats>> {
+    Ok(crate::service::db::file_list::get_column_stats(file)
+        .await?
+        .map(|column_ranges| FileStats { column_ranges }))
+}
+
+/// list every file ingested for `org_id`/`stream_name` whose time range
+/// overlaps `[time_min, time_max]`. This is the existing file-list lookup
+/// that `storage::get_file_list` layers matcher- and statistics-based
+/// pruning on top of.
+pub async fn get_file_list(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: Option<crate::meta::StreamType>,
+    time_min: i64,
+    time_max: i64,
+) -> Result<Vec<String>> {
+    crate::service::db::file_list::get(org_id, stream_name, stream_type, time_min, time_max).await
+}
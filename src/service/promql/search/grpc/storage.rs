@@ -13,27 +13,252 @@
 // limitations under the License.
 
 use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
 use datafusion::{
-    arrow::datatypes::Schema,
-    datasource::file_format::file_type::FileType,
+    arrow::datatypes::{DataType, Field, Schema, SchemaRef},
+    datasource::{
+        file_format::parquet::ParquetFormat,
+        listing::{ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl},
+        physical_plan::{
+            parquet::{ParquetExecBuilder, ParquetFileReaderFactory},
+            FileMeta, FileScanConfig,
+        },
+        TableProvider as DfTableProvider, TableType,
+    },
     error::{DataFusionError, Result},
+    execution::{context::SessionState, object_store::ObjectStoreUrl},
+    logical_expr::Expr as DfExpr,
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        ExecutionPlan,
+    },
     prelude::SessionContext,
 };
-use promql_parser::parser;
-use std::sync::Arc;
+use futures::future::BoxFuture;
+use object_store::ObjectStore;
+use once_cell::sync::Lazy;
+use parquet::arrow::async_reader::{AsyncFileReader, ParquetObjectReader};
+use parquet::file::footer;
+use parquet::file::metadata::ParquetMetaData;
+use promql_parser::{label::MatchOp, parser};
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, UNIX_EPOCH};
 use tokio::sync::Semaphore;
 
 use crate::handler::grpc::cluster_rpc;
-use crate::infra::{cache::file_data, config::CONFIG};
-use crate::meta::{search::Session as SearchSession, stream::StreamParams, StreamType};
+use crate::infra::{cache::file_data, config::CONFIG, storage};
+use crate::meta::{stream::StreamParams, StreamType};
 use crate::service::promql::{value, TableProvider};
-use crate::service::search::datafusion::{exec::register_table, storage::file_list::SessionType};
 use crate::service::search::match_source;
+use crate::service::usage::{record_query_usage, QueryUsage};
 use crate::service::{db, file_list, promql, search};
 
+/// max number of file footers kept in `FOOTER_CACHE`; once exceeded the
+/// least-recently-inserted entry is evicted so the cache stays "small" as
+/// intended rather than growing without bound for the life of the process
+const FOOTER_CACHE_CAPACITY: usize = 4096;
+
+/// bounded, insertion-order-evicted cache of Parquet footer metadata keyed by
+/// file name, so a `direct_read` query doesn't re-fetch the footer of a file
+/// it already touched in an earlier `search` invocation. `order` tracks
+/// insertion order for eviction; both fields live behind the same lock so
+/// they never drift apart.
+struct FooterCache {
+    /// parsed metadata plus the footer's on-disk byte length (serialized
+    /// thrift size + the 8-byte length+magic trailer) -- the two are
+    /// unrelated sizes and both get cached so each has its own reader
+    entries: HashMap<String, (Arc<ParquetMetaData>, usize)>,
+    order: VecDeque<String>,
+}
+
+impl FooterCache {
+    fn get(&self, file: &str) -> Option<(Arc<ParquetMetaData>, usize)> {
+        self.entries.get(file).cloned()
+    }
+
+    fn insert(&mut self, file: String, meta: Arc<ParquetMetaData>, footer_len: usize) {
+        if self
+            .entries
+            .insert(file.clone(), (meta, footer_len))
+            .is_none()
+        {
+            self.order.push_back(file);
+            if self.order.len() > FOOTER_CACHE_CAPACITY {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+static FOOTER_CACHE: Lazy<Mutex<FooterCache>> = Lazy::new(|| {
+    Mutex::new(FooterCache {
+        entries: HashMap::new(),
+        order: VecDeque::new(),
+    })
+});
+
+/// per-query scan totals, filled in by `create_context` as it registers each
+/// stream's files and by the `MeteredTableProvider` it installs as those
+/// tables are actually scanned; `search` reads this back once `engine.exec`
+/// returns (or errors) to feed the usage-metering counters.
+#[derive(Default)]
+struct ScanStats {
+    stream_name: String,
+    file_count: u64,
+    /// physical plans handed out by `MeteredTableProvider::scan`, read back
+    /// for their `ExecutionPlan::metrics()` once `engine.exec` has finished
+    /// polling them to completion
+    plans: Vec<Arc<dyn ExecutionPlan>>,
+}
+
+/// wraps the stream's table provider (a `ListingTable`, or a
+/// `DirectParquetTableProvider` under `direct_read`) so every physical plan
+/// DataFusion builds from it is
+/// retained in `ScanStats`; that's what lets `search` read real
+/// bytes-scanned/rows-produced numbers out of the plan's `MetricsSet` after
+/// the query finishes, instead of reporting them as always zero.
+struct MeteredTableProvider {
+    inner: Arc<dyn DfTableProvider>,
+    stats: Arc<Mutex<ScanStats>>,
+}
+
+#[async_trait]
+impl DfTableProvider for MeteredTableProvider {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[DfExpr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let plan = self.inner.scan(state, projection, filters, limit).await?;
+        self.stats.lock().unwrap().plans.push(plan.clone());
+        Ok(plan)
+    }
+}
+
+/// sum the `bytes_scanned`/`output_rows` metrics of `plan` and every one of
+/// its children. DataFusion's `ParquetExec` records `bytes_scanned` per file
+/// group it actually reads, so this reflects the real column/row-group
+/// pruned scan, not the on-disk size of the files the query touched.
+fn sum_plan_metrics(plan: &Arc<dyn ExecutionPlan>) -> (u64, u64) {
+    let mut bytes = 0u64;
+    let mut rows = 0u64;
+    if let Some(metrics) = plan.metrics() {
+        bytes += metrics_sum(&metrics, "bytes_scanned");
+        rows += metrics.output_rows().unwrap_or(0) as u64;
+    }
+    for child in plan.children() {
+        let (b, r) = sum_plan_metrics(&child);
+        bytes += b;
+        rows += r;
+    }
+    (bytes, rows)
+}
+
+fn metrics_sum(metrics: &MetricsSet, name: &str) -> u64 {
+    metrics
+        .sum_by_name(name)
+        .map(|v| v.as_usize() as u64)
+        .unwrap_or(0)
+}
+
+/// a single PromQL label matcher, kept richer than `(&str, &str)` so
+/// `get_file_list` can prune on regex and negation too, not just equality
+#[derive(Debug, Clone)]
+struct LabelMatcher {
+    name: String,
+    op: MatchOp,
+    value: String,
+}
+
+/// walk every vector selector in `expr` and collect its label matchers, so
+/// they can be pushed down into file-list pruning alongside the time range
+fn extract_matchers(expr: &parser::Expr) -> Vec<LabelMatcher> {
+    use parser::Expr::*;
+
+    let mut matchers = Vec::new();
+    match expr {
+        VectorSelector(sel) => {
+            for m in sel.matchers.matchers.iter() {
+                matchers.push(LabelMatcher {
+                    name: m.name.clone(),
+                    op: m.op,
+                    value: m.value.clone(),
+                });
+            }
+        }
+        MatrixSelector(sel) => {
+            for m in sel.vs.matchers.matchers.iter() {
+                matchers.push(LabelMatcher {
+                    name: m.name.clone(),
+                    op: m.op,
+                    value: m.value.clone(),
+                });
+            }
+        }
+        Unary(u) => matchers.extend(extract_matchers(&u.expr)),
+        Binary(b) => {
+            matchers.extend(extract_matchers(&b.lhs));
+            matchers.extend(extract_matchers(&b.rhs));
+        }
+        Paren(p) => matchers.extend(extract_matchers(&p.expr)),
+        Subquery(s) => matchers.extend(extract_matchers(&s.expr)),
+        Call(c) => {
+            for arg in c.args.args.iter() {
+                matchers.extend(extract_matchers(arg));
+            }
+        }
+        Aggregate(a) => matchers.extend(extract_matchers(&a.expr)),
+        Extension(_) | NumberLiteral(_) | StringLiteral(_) => {}
+    }
+    matchers
+}
+
+/// returns false if a file's recorded per-column min/max statistics prove it
+/// cannot contain a row matching every matcher, true otherwise. Pruning must be
+/// conservative: any matcher we can't evaluate from statistics alone (regex,
+/// negation, or a column with no recorded stats) keeps the file.
+async fn file_could_match(file: &str, matchers: &[LabelMatcher]) -> bool {
+    let stats = match file_list::get_file_stats(file).await {
+        Ok(Some(stats)) => stats,
+        _ => return true,
+    };
+    for m in matchers {
+        if m.op != MatchOp::Equal {
+            // =~, !~ and != can't be proven false from a min/max range alone
+            continue;
+        }
+        if let Some((min, max)) = stats.column_range(&m.name) {
+            if m.value.as_str() < min.as_str() || m.value.as_str() > max.as_str() {
+                return false;
+            }
+        }
+    }
+    true
+}
+
 struct StorageProvider {
     session_id: String,
+    stats: Arc<Mutex<ScanStats>>,
+    matchers: Vec<LabelMatcher>,
 }
 
 #[async_trait]
@@ -45,83 +270,129 @@ impl TableProvider for StorageProvider {
         time_range: (i64, i64),
         filters: &[(&str, &str)],
     ) -> Result<(SessionContext, Arc<Schema>)> {
-        // get file list
-        let files = get_file_list(org_id, stream_name, time_range, filters).await?;
+        // get file list, pruned by both the coarse source filters and the
+        // richer label matchers parsed from the PromQL expression
+        let files = get_file_list(org_id, stream_name, time_range, filters, &self.matchers).await?;
         let file_count = files.len();
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.stream_name = stream_name.to_string();
+            stats.file_count += file_count as u64;
+        }
         if files.is_empty() {
             return Ok((SessionContext::new(), Arc::new(Schema::empty())));
         }
 
-        // load files to local cache
-        let mut tasks = Vec::new();
-        let semaphore = std::sync::Arc::new(Semaphore::new(CONFIG.limit.query_thread_num));
-        for file in files.iter() {
-            let file = file.clone();
-            let permit = semaphore.clone().acquire_owned().await.unwrap();
-            let task: tokio::task::JoinHandle<Result<(), anyhow::Error>> =
-                tokio::task::spawn(async move {
-                    if !file_data::exist(&file).unwrap_or_default() {
-                        if let Err(e) = file_data::download(&file).await {
-                            log::error!("storage->search: load file {}, err: {}", &file, e);
-                        }
-                    };
-                    drop(permit);
-                    Ok(())
-                });
-            tasks.push(task);
-        }
+        let footer_size_hint = if CONFIG.storage.direct_read {
+            // metadata-only: footers are prefetched through the ObjectStore we
+            // register below, no file ever touches local disk
+            let hint = prefetch_remote_footers(&files).await?;
+            log::info!(
+                "[TRACE] promql->search->storage: direct_read {} files, no local download",
+                file_count
+            );
+            hint
+        } else {
+            // load files to local cache
+            let mut tasks = Vec::new();
+            let semaphore = std::sync::Arc::new(Semaphore::new(CONFIG.limit.query_thread_num));
+            for file in files.iter() {
+                let file = file.clone();
+                let permit = semaphore.clone().acquire_owned().await.unwrap();
+                let task: tokio::task::JoinHandle<Result<(), anyhow::Error>> =
+                    tokio::task::spawn(async move {
+                        if !file_data::exist(&file).unwrap_or_default() {
+                            if let Err(e) = file_data::download(&file).await {
+                                log::error!("storage->search: load file {}, err: {}", &file, e);
+                            }
+                        };
+                        drop(permit);
+                        Ok(())
+                    });
+                tasks.push(task);
+            }
 
-        for task in tasks {
-            match task.await {
-                Ok(ret) => {
-                    if let Err(err) = ret {
+            for task in tasks {
+                match task.await {
+                    Ok(ret) => {
+                        if let Err(err) = ret {
+                            return Err(DataFusionError::Execution(err.to_string()));
+                        }
+                    }
+                    Err(err) => {
                         return Err(DataFusionError::Execution(err.to_string()));
                     }
-                }
-                Err(err) => {
-                    return Err(DataFusionError::Execution(err.to_string()));
-                }
-            };
-        }
-        log::info!(
-            "[TRACE] promql->search->storage: load files {} done",
-            file_count
-        );
-
-        // fetch all schema versions, get latest schema
-        let stream_type = StreamType::Metrics;
-        let schema = match db::schema::get(org_id, stream_name, Some(stream_type)).await {
-            Ok(schema) => schema,
-            Err(err) => {
-                log::error!("get schema error: {}", err);
-                return Err(datafusion::error::DataFusionError::Execution(
-                    err.to_string(),
-                ));
+                };
             }
+            log::info!(
+                "[TRACE] promql->search->storage: load files {} done",
+                file_count
+            );
+            None
         };
+
+        // fetch every schema version the file set can span and union them into
+        // one superset schema, so files written before a label/field was added
+        // (or dropped) don't trip column-not-found errors. Registering this as
+        // the table's schema (rather than each file's own physical schema)
+        // is what makes DataFusion's own per-file `SchemaAdapter` cast
+        // Int64<->Float64 columns and null-fill columns a given file lacks.
+        let stream_type = StreamType::Metrics;
+        let schema = merge_stream_schemas(org_id, stream_name, stream_type, time_range).await?;
         let schema = Arc::new(
             schema
                 .to_owned()
                 .with_metadata(std::collections::HashMap::new()),
         );
-        let session = SearchSession {
-            id: self.session_id.clone(),
-            data_type: SessionType::Storage,
+
+        let ctx = SessionContext::new();
+        let object_store_url = if CONFIG.storage.direct_read {
+            storage::get_object_store_url()
+        } else {
+            ObjectStoreUrl::parse("file://").map_err(|e| DataFusionError::External(Box::new(e)))?
         };
+        if CONFIG.storage.direct_read {
+            ctx.runtime_env()
+                .register_object_store(object_store_url.as_ref(), storage::get_object_store());
+        }
 
-        register_table(
-            &session,
-            StreamParams {
-                org_id,
-                stream_name,
-                stream_type,
-            },
-            Some(schema),
+        // direct_read builds its own ParquetExec (via DirectParquetTableProvider)
+        // so CachedParquetFileReaderFactory can serve the footers prefetched
+        // above straight out of FOOTER_CACHE; ListingTable has no hook for a
+        // custom reader factory, so it would silently re-parse every footer
+        // the prefetch step just fetched, doubling the remote reads.
+        let provider: Arc<dyn DfTableProvider> = if CONFIG.storage.direct_read {
+            let object_store = storage::get_object_store();
+            let partitioned_files = build_partitioned_files(&files, &object_store).await?;
+            Arc::new(DirectParquetTableProvider {
+                schema: schema.clone(),
+                object_store_url: object_store_url.clone(),
+                files: partitioned_files,
+                reader_factory: Arc::new(CachedParquetFileReaderFactory {
+                    store: object_store,
+                }),
+                footer_size_hint,
+            })
+        } else {
+            let table_urls = build_table_urls(&files, &object_store_url, false)?;
+            let format = ParquetFormat::default().with_metadata_size_hint(footer_size_hint);
+            let listing_options =
+                ListingOptions::new(Arc::new(format)).with_file_extension(".parquet");
+            let config = ListingTableConfig::new_with_multi_paths(table_urls)
+                .with_listing_options(listing_options)
+                .with_schema((*schema).clone());
+            Arc::new(ListingTable::try_new(config)?)
+        };
+
+        ctx.register_table(
             stream_name,
-            &files,
-            FileType::PARQUET,
-        )
-        .await
+            Arc::new(MeteredTableProvider {
+                inner: provider,
+                stats: self.stats.clone(),
+            }),
+        )?;
+
+        Ok((ctx, schema))
     }
 }
 
@@ -136,25 +407,82 @@ pub async fn search(
         DataFusionError::Execution(e)
     })?;
 
+    // this only snaps the two range *endpoints* onto a `step` boundary
+    // counted from local midnight in `timezone`; it does NOT make every intermediate step
+    // DST-aware. The PromQL engine still advances from `start` to `end` by
+    // a fixed `step` duration in absolute (UTC) time, same as upstream
+    // Prometheus, so a step that falls after a DST transition drifts by the
+    // offset change instead of landing back on local midnight. True
+    // per-step local-time alignment would require the engine's stepping
+    // loop itself to recompute boundaries in local time, which is out of
+    // this function's (and this file's) scope. Leaving `timezone` empty
+    // skips this entirely and keeps today's UTC stepping byte-for-byte.
+    let (start, end) = if query.timezone.is_empty() {
+        (query.start, query.end)
+    } else {
+        (
+            align_to_timezone(query.start, query.step, &query.timezone)?,
+            align_to_timezone(query.end, query.step, &query.timezone)?,
+        )
+    };
+    let lookback_delta = if query.lookback_delta > 0 {
+        query.lookback_delta as u64
+    } else {
+        300 // 5m, today's fixed default
+    };
+
+    // collect the label matchers up front so file-list pruning can use them;
+    // `eval_stmt` takes ownership of `prom_expr` below
+    let matchers = extract_matchers(&prom_expr);
+
     let eval_stmt = parser::EvalStmt {
         expr: prom_expr,
         start: UNIX_EPOCH
-            .checked_add(Duration::from_micros(query.start as _))
+            .checked_add(Duration::from_micros(start as _))
             .unwrap(),
         end: UNIX_EPOCH
-            .checked_add(Duration::from_micros(query.end as _))
+            .checked_add(Duration::from_micros(end as _))
             .unwrap(),
         interval: Duration::from_micros(query.step as _),
-        lookback_delta: Duration::from_secs(300), // 5m
+        lookback_delta: Duration::from_secs(lookback_delta),
     };
 
+    let stats = Arc::new(Mutex::new(ScanStats::default()));
     let mut engine = promql::QueryEngine::new(
         org_id,
         StorageProvider {
             session_id: session_id.to_string(),
+            stats: stats.clone(),
+            matchers,
         },
     );
-    let data = engine.exec(eval_stmt).await?;
+    let started_at = std::time::Instant::now();
+    let result = engine.exec(eval_stmt).await;
+    let duration = started_at.elapsed();
+
+    // usage is recorded on the error path too: a failed-but-expensive scan
+    // still touched object storage and should count against the org's quota.
+    // By now `engine.exec` has fully polled every plan `MeteredTableProvider`
+    // handed out, so their `ExecutionPlan::metrics()` are populated.
+    let stats = stats.lock().unwrap();
+    let (bytes_scanned, rows_produced) = stats
+        .plans
+        .iter()
+        .map(sum_plan_metrics)
+        .fold((0u64, 0u64), |(b, r), (pb, pr)| (b + pb, r + pr));
+    record_query_usage(QueryUsage {
+        org_id: org_id.to_string(),
+        stream_name: stats.stream_name.clone(),
+        query: query.query.clone(),
+        time_range: (query.start, query.end),
+        bytes_scanned,
+        rows_produced,
+        file_count: stats.file_count,
+        duration,
+    })
+    .await;
+
+    let data = result?;
 
     // clear session
     search::datafusion::storage::file_list::clear(session_id)
@@ -164,12 +492,383 @@ pub async fn search(
     Ok(data)
 }
 
+/// shift `micros` back onto the nearest `step_micros` boundary measured from
+/// local midnight in `tz`, so a range query's start/end line up with that
+/// timezone's wall clock instead of UTC's. DST gaps/overlaps can make a local
+/// wall-clock time ambiguous or nonexistent: an ambiguous boundary resolves
+/// to its earliest instant, and a nonexistent one moves forward by whole
+/// steps to the first boundary that exists (which can lie after `micros`),
+/// so that successive calls stay monotonic.
+fn align_to_timezone(micros: i64, step_micros: i64, tz: &str) -> Result<i64> {
+    if step_micros <= 0 {
+        return Ok(micros);
+    }
+    let tz: chrono_tz::Tz = tz
+        .parse()
+        .map_err(|_| DataFusionError::Execution(format!("invalid timezone: {tz}")))?;
+
+    let start_utc = Utc
+        .timestamp_micros(micros)
+        .single()
+        .ok_or_else(|| DataFusionError::Execution(format!("timestamp out of range: {micros}")))?;
+    let local = start_utc.with_timezone(&tz);
+    let midnight = local.date_naive().and_hms_opt(0, 0, 0).unwrap();
+    let since_midnight = local.naive_local() - midnight;
+    let since_midnight_us = since_midnight.num_microseconds().unwrap_or(0);
+    let aligned_us = since_midnight_us - since_midnight_us.rem_euclid(step_micros);
+    let aligned_naive = midnight + chrono::Duration::microseconds(aligned_us);
+
+    let aligned = match tz.from_local_datetime(&aligned_naive) {
+        chrono::LocalResult::Single(dt) => dt,
+        chrono::LocalResult::Ambiguous(earliest, _latest) => earliest,
+        chrono::LocalResult::None => {
+            // the aligned wall-clock time falls inside a DST gap; nudge
+            // forward by the step until we land on a time that exists
+            let mut naive = aligned_naive;
+            loop {
+                naive += chrono::Duration::microseconds(step_micros);
+                if let chrono::LocalResult::Single(dt) = tz.from_local_datetime(&naive) {
+                    break dt;
+                }
+            }
+        }
+    };
+
+    Ok(aligned.with_timezone(&Utc).timestamp_micros())
+}
+
+/// fetch every schema version registered for `stream_name` in `time_range`
+/// and fold them into a single superset schema: fields are unioned in the
+/// order they were first seen (oldest version first), so canonical columns
+/// like `_timestamp`/`value` stay where `db::schema::get` always put them
+/// instead of being reshuffled alphabetically, and compatible-but-differing
+/// types (e.g. `Int64` vs `Float64`) are widened to the wider type.
+/// Registering this union as the table's schema, rather than each
+/// file's own physical schema, is what makes DataFusion's per-file
+/// `SchemaAdapter` cast a file's narrower physical type (e.g. `Int64`) up to
+/// this schema's type and null-fill any column the file doesn't have. Every
+/// merged field is forced nullable for that last reason: any given file may
+/// simply lack a column another version introduced, and the adapter can only
+/// null-fill it if the superset schema allows nulls there.
+async fn merge_stream_schemas(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    time_range: (i64, i64),
+) -> Result<Schema> {
+    let versions =
+        match db::schema::get_versions(org_id, stream_name, Some(stream_type), time_range).await {
+            Ok(versions) if !versions.is_empty() => versions,
+            Ok(_) => {
+                return Err(DataFusionError::Execution(format!(
+                    "no schema found for stream {stream_name}"
+                )))
+            }
+            Err(err) => {
+                log::error!("get schema error: {}", err);
+                return Err(DataFusionError::Execution(err.to_string()));
+            }
+        };
+
+    let mut merged: HashMap<String, Field> = HashMap::new();
+    // tracks first-seen order separately from `merged` so the result keeps
+    // the stream's canonical column order instead of HashMap's arbitrary one
+    let mut order: Vec<String> = Vec::new();
+    for version in versions {
+        for field in version.fields() {
+            match merged.get(field.name()) {
+                None => {
+                    order.push(field.name().clone());
+                    // forced nullable regardless of this version's own
+                    // nullability: any *other* version's file may simply
+                    // lack this column, and the per-file SchemaAdapter
+                    // null-fills it -- which requires the superset field to
+                    // allow nulls, or the adapter produces nulls in a
+                    // column this schema claims can never contain one
+                    merged.insert(
+                        field.name().clone(),
+                        Field::new(field.name(), field.data_type().clone(), true),
+                    );
+                }
+                Some(existing) if existing.data_type() == field.data_type() => {}
+                Some(existing) => {
+                    let widened =
+                        widen_type(existing.data_type(), field.data_type()).ok_or_else(|| {
+                            DataFusionError::Execution(format!(
+                                "field {} has incompatible schema versions: {:?} vs {:?}",
+                                field.name(),
+                                existing.data_type(),
+                                field.data_type()
+                            ))
+                        })?;
+                    merged.insert(
+                        field.name().clone(),
+                        Field::new(field.name(), widened, true),
+                    );
+                }
+            }
+        }
+    }
+
+    let fields: Vec<Field> = order
+        .into_iter()
+        .map(|name| merged.remove(&name).unwrap())
+        .collect();
+    Ok(Schema::new(fields))
+}
+
+/// widen two physical types into a common supertype, or `None` if they
+/// cannot be reconciled without loss (e.g. `Utf8` vs `Int64`)
+fn widen_type(a: &DataType, b: &DataType) -> Option<DataType> {
+    use DataType::*;
+    match (a, b) {
+        (Float64, Int64) | (Int64, Float64) | (Float64, Float64) => Some(Float64),
+        (Int64, Int64) => Some(Int64),
+        (a, b) if a == b => Some(a.clone()),
+        _ => None,
+    }
+}
+
+/// number of trailing bytes every Parquet file reserves for the footer
+/// length (a little-endian `u32` byte count for the serialized thrift
+/// metadata) followed by the 4-byte "PAR1" magic
+const PARQUET_FOOTER_TRAILER_LEN: usize = 8;
+
+/// read the last 8 bytes of a file to recover the *on-disk* footer length:
+/// `metadata_len + PARQUET_FOOTER_TRAILER_LEN`. This is the number
+/// `with_metadata_size_hint` wants -- how many trailing bytes to fetch in
+/// one range request to cover the whole serialized footer -- which is
+/// unrelated to `ParquetMetaData::memory_size()`, the heap size of the
+/// *deserialized* struct we also cache.
+fn footer_len<R: parquet::file::reader::ChunkReader>(reader: &R) -> anyhow::Result<usize> {
+    let file_len = reader.len();
+    if file_len < PARQUET_FOOTER_TRAILER_LEN as u64 {
+        return Err(anyhow::anyhow!(
+            "file too small to contain a parquet footer"
+        ));
+    }
+    let trailer = reader.get_bytes(file_len - PARQUET_FOOTER_TRAILER_LEN as u64, 8)?;
+    if &trailer[4..8] != b"PAR1" {
+        return Err(anyhow::anyhow!("not a parquet file (bad footer magic)"));
+    }
+    let metadata_len = u32::from_le_bytes(trailer[0..4].try_into().unwrap()) as usize;
+    Ok(metadata_len + PARQUET_FOOTER_TRAILER_LEN)
+}
+
+/// fetch just the Parquet footer for each file over HTTP byte-range requests
+/// and stash it in `FOOTER_CACHE`, without downloading the file body, then
+/// returns a `metadata_size_hint`: the largest cached on-disk footer length
+/// across `files`, so the `ParquetExec` built by `DirectParquetTableProvider`
+/// fetches the whole footer of every file in one byte-range
+/// request instead of guessing and re-fetching. `None` means no file had a
+/// usable cached footer yet.
+async fn prefetch_remote_footers(files: &[String]) -> Result<Option<usize>> {
+    let mut tasks = Vec::new();
+    let semaphore = std::sync::Arc::new(Semaphore::new(CONFIG.limit.query_thread_num));
+    for file in files.iter() {
+        if FOOTER_CACHE.lock().unwrap().get(file).is_some() {
+            continue;
+        }
+        let file = file.clone();
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let task: tokio::task::JoinHandle<Result<(), anyhow::Error>> =
+            tokio::task::spawn(async move {
+                let result: Result<(), anyhow::Error> = async {
+                    let object = storage::get_object_store();
+                    let reader = storage::get_file_reader(&object, &file).await?;
+                    let len = footer_len(&reader)?;
+                    let meta = footer::parse_metadata(&reader)?;
+                    FOOTER_CACHE
+                        .lock()
+                        .unwrap()
+                        .insert(file.clone(), Arc::new(meta), len);
+                    Ok(())
+                }
+                .await;
+                if let Err(e) = &result {
+                    log::error!("storage->search: prefetch footer {}, err: {}", &file, e);
+                }
+                drop(permit);
+                result
+            });
+        tasks.push(task);
+    }
+
+    for task in tasks {
+        match task.await {
+            Ok(ret) => {
+                if let Err(err) = ret {
+                    return Err(DataFusionError::Execution(err.to_string()));
+                }
+            }
+            Err(err) => {
+                return Err(DataFusionError::Execution(err.to_string()));
+            }
+        };
+    }
+
+    let cache = FOOTER_CACHE.lock().unwrap();
+    let hint = files
+        .iter()
+        .filter_map(|f| cache.get(f))
+        .map(|(_, footer_len)| footer_len)
+        .max();
+    Ok(hint)
+}
+
+/// build the `ListingTableUrl`s DataFusion scans for `files`: remote object
+/// keys under the registered ObjectStore's URL when `direct_read` is set, or
+/// local cache paths under `file://` otherwise.
+fn build_table_urls(
+    files: &[String],
+    object_store_url: &ObjectStoreUrl,
+    direct_read: bool,
+) -> Result<Vec<ListingTableUrl>> {
+    files
+        .iter()
+        .map(|file| {
+            let uri = if direct_read {
+                format!("{}{}", object_store_url.as_str(), file)
+            } else {
+                format!("file://{}", file_data::local_path(file).display())
+            };
+            ListingTableUrl::parse(&uri).map_err(|e| DataFusionError::External(Box::new(e)))
+        })
+        .collect()
+}
+
+/// serves Parquet footer reads out of `FOOTER_CACHE` instead of letting
+/// `ParquetExec` re-parse them on every scan. Row-group byte-range reads
+/// still go straight through to the real object store; only `get_metadata`
+/// is intercepted, so this is the actual consumer `FOOTER_CACHE` was missing
+/// -- without it the cache only ever served `prefetch_remote_footers`
+/// itself, and every `direct_read` query re-read each footer a second time
+/// once `ParquetExec` opened the file for real.
+struct CachedParquetFileReaderFactory {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl ParquetFileReaderFactory for CachedParquetFileReaderFactory {
+    fn create_reader(
+        &self,
+        _partition_index: usize,
+        file_meta: FileMeta,
+        metadata_size_hint: Option<usize>,
+        _metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Box<dyn AsyncFileReader + Send>> {
+        let cached = FOOTER_CACHE
+            .lock()
+            .unwrap()
+            .get(file_meta.object_meta.location.as_ref())
+            .map(|(meta, _)| meta);
+        let mut inner = ParquetObjectReader::new(self.store.clone(), file_meta.object_meta);
+        if let Some(hint) = metadata_size_hint {
+            inner = inner.with_footer_size_hint(hint);
+        }
+        Ok(Box::new(CachedParquetReader { inner, cached }))
+    }
+}
+
+struct CachedParquetReader {
+    inner: ParquetObjectReader,
+    cached: Option<Arc<ParquetMetaData>>,
+}
+
+impl AsyncFileReader for CachedParquetReader {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, parquet::errors::Result<Bytes>> {
+        self.inner.get_bytes(range)
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, parquet::errors::Result<Vec<Bytes>>> {
+        self.inner.get_byte_ranges(ranges)
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, parquet::errors::Result<Arc<ParquetMetaData>>> {
+        if let Some(meta) = self.cached.clone() {
+            return Box::pin(async move { Ok(meta) });
+        }
+        self.inner.get_metadata()
+    }
+}
+
+/// `TableProvider` over a fixed, already-resolved set of remote Parquet
+/// files, used for `direct_read` instead of `ListingTable` so a
+/// `CachedParquetFileReaderFactory` can be wired into the `ParquetExec`.
+/// `ListingTable` builds its own `ParquetExec` internally with no hook for a
+/// custom reader factory, which is exactly why the footer cache went
+/// unconsulted before this change.
+struct DirectParquetTableProvider {
+    schema: SchemaRef,
+    object_store_url: ObjectStoreUrl,
+    files: Vec<datafusion::datasource::physical_plan::PartitionedFile>,
+    reader_factory: Arc<CachedParquetFileReaderFactory>,
+    /// largest cached on-disk footer length across `files`, from
+    /// `prefetch_remote_footers`; threaded through to the `ParquetExec` so
+    /// it fetches the whole footer in one range request on a cache miss
+    footer_size_hint: Option<usize>,
+}
+
+#[async_trait]
+impl DfTableProvider for DirectParquetTableProvider {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[DfExpr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let config = FileScanConfig::new(self.object_store_url.clone(), self.schema.clone())
+            .with_file_group(self.files.clone())
+            .with_projection(projection.cloned())
+            .with_limit(limit);
+        let exec = ParquetExecBuilder::new(config)
+            .with_parquet_file_reader_factory(self.reader_factory.clone())
+            .with_metadata_size_hint(self.footer_size_hint)
+            .build();
+        Ok(Arc::new(exec))
+    }
+}
+
+/// `HEAD` every file to get the `ObjectMeta` `FileScanConfig`/`ParquetExec`
+/// need (size, last-modified); this is metadata-only, same as the footer
+/// prefetch, so it doesn't touch the file body either.
+async fn build_partitioned_files(
+    files: &[String],
+    store: &Arc<dyn ObjectStore>,
+) -> Result<Vec<datafusion::datasource::physical_plan::PartitionedFile>> {
+    let mut out = Vec::with_capacity(files.len());
+    for file in files {
+        let object_meta = store
+            .head(&object_store::path::Path::from(file.as_str()))
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        out.push(datafusion::datasource::physical_plan::PartitionedFile::from(object_meta));
+    }
+    Ok(out)
+}
+
 #[inline]
 async fn get_file_list(
     org_id: &str,
     stream_name: &str,
     time_range: (i64, i64),
     filters: &[(&str, &str)],
+    matchers: &[LabelMatcher],
 ) -> Result<Vec<String>> {
     let (time_min, time_max) = time_range;
     let results = match file_list::get_file_list(
@@ -192,7 +891,7 @@ async fn get_file_list(
 
     let mut files = Vec::new();
     for file in results {
-        if match_source(
+        if !match_source(
             StreamParams {
                 org_id,
                 stream_name,
@@ -206,8 +905,145 @@ async fn get_file_list(
         )
         .await
         {
-            files.push(file.clone());
+            continue;
+        }
+        if !file_could_match(&file, matchers).await {
+            continue;
         }
+        files.push(file.clone());
     }
     Ok(files)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod schema_merge_tests {
+    use super::*;
+
+    #[test]
+    fn widen_type_promotes_int64_and_float64_to_float64() {
+        assert_eq!(
+            widen_type(&DataType::Int64, &DataType::Float64),
+            Some(DataType::Float64)
+        );
+        assert_eq!(
+            widen_type(&DataType::Float64, &DataType::Int64),
+            Some(DataType::Float64)
+        );
+        assert_eq!(
+            widen_type(&DataType::Float64, &DataType::Float64),
+            Some(DataType::Float64)
+        );
+    }
+
+    #[test]
+    fn widen_type_keeps_identical_types() {
+        assert_eq!(
+            widen_type(&DataType::Int64, &DataType::Int64),
+            Some(DataType::Int64)
+        );
+        assert_eq!(
+            widen_type(&DataType::Utf8, &DataType::Utf8),
+            Some(DataType::Utf8)
+        );
+    }
+
+    #[test]
+    fn widen_type_rejects_incompatible_types() {
+        assert_eq!(widen_type(&DataType::Utf8, &DataType::Int64), None);
+        assert_eq!(widen_type(&DataType::Boolean, &DataType::Float64), None);
+    }
+}
+
+#[cfg(test)]
+mod align_to_timezone_tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn micros(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, s)
+            .unwrap()
+            .timestamp_micros()
+    }
+
+    #[test]
+    fn zero_step_is_a_no_op() {
+        let input = micros(2024, 6, 15, 13, 37, 0);
+        assert_eq!(
+            align_to_timezone(input, 0, "America/New_York").unwrap(),
+            input
+        );
+    }
+
+    #[test]
+    fn aligns_to_local_midnight_on_a_normal_day() {
+        // 2024-06-15 13:37:00 America/New_York (no DST transition nearby),
+        // daily step should floor to that day's local midnight
+        let input = micros(2024, 6, 15, 17, 37, 0); // 13:37 EDT (UTC-4)
+        let day = 24 * 60 * 60 * 1_000_000;
+        let aligned = align_to_timezone(input, day, "America/New_York").unwrap();
+        let expected = micros(2024, 6, 15, 4, 0, 0); // 2024-06-15 00:00 EDT = 04:00 UTC
+        assert_eq!(aligned, expected);
+    }
+
+    #[test]
+    fn spring_forward_gap_nudges_to_the_next_valid_instant() {
+        // 2024-03-10: US Eastern clocks jump 01:59:59 EST -> 03:00:00 EDT.
+        // 07:05 UTC is 03:05 EDT; flooring to a 2h grid from local midnight
+        // lands on the nonexistent local time 02:00, which must nudge
+        // forward (by the step) to the next valid instant: 04:00 EDT.
+        let input = micros(2024, 3, 10, 7, 5, 0);
+        let two_hours = 2 * 60 * 60 * 1_000_000;
+        let aligned = align_to_timezone(input, two_hours, "America/New_York").unwrap();
+        let expected = micros(2024, 3, 10, 8, 0, 0); // 04:00 EDT = 08:00 UTC
+        assert_eq!(aligned, expected);
+    }
+
+    #[test]
+    fn fall_back_ambiguous_time_resolves_to_the_earliest_instant() {
+        // 2024-11-03: local 01:00-01:59 occurs twice (02:00 EDT -> 01:00
+        // EST). 06:45 UTC is the *second* occurrence of 01:45 (EST);
+        // flooring to an hourly grid lands on the ambiguous local time
+        // 01:00, which must resolve to its earliest (EDT) interpretation.
+        let input = micros(2024, 11, 3, 6, 45, 0);
+        let one_hour = 60 * 60 * 1_000_000;
+        let aligned = align_to_timezone(input, one_hour, "America/New_York").unwrap();
+        let expected = micros(2024, 11, 3, 5, 0, 0); // 01:00 EDT (earliest) = 05:00 UTC
+        assert_eq!(aligned, expected);
+    }
+
+    #[test]
+    fn out_of_range_timestamp_errors_instead_of_panicking() {
+        assert!(align_to_timezone(i64::MAX, 1, "America/New_York").is_err());
+    }
+
+    #[test]
+    fn invalid_timezone_errors() {
+        assert!(align_to_timezone(0, 1, "Not/A_Zone").is_err());
+    }
+}
+
+#[cfg(test)]
+mod extract_matchers_tests {
+    use super::*;
+
+    #[test]
+    fn collects_matchers_from_a_vector_selector() {
+        let expr = parser::parse(r#"up{job="api", env!="staging"}"#).unwrap();
+        let matchers = extract_matchers(&expr);
+        assert_eq!(matchers.len(), 2);
+        assert!(matchers
+            .iter()
+            .any(|m| m.name == "job" && m.op == MatchOp::Equal && m.value == "api"));
+        assert!(matchers
+            .iter()
+            .any(|m| m.name == "env" && m.op == MatchOp::NotEqual && m.value == "staging"));
+    }
+
+    #[test]
+    fn collects_matchers_through_binary_and_aggregate_expressions() {
+        let expr = parser::parse(r#"sum(up{job="api"}) / sum(up{job="db"})"#).unwrap();
+        let matchers = extract_matchers(&expr);
+        assert_eq!(matchers.len(), 2);
+        assert!(matchers.iter().all(|m| m.name == "job"));
+    }
+}
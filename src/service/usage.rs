@@ -0,0 +1,131 @@
+// Copyright 2022 Zinc Labs Inc. and Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+
+/// bytes scanned by object-storage search queries, labeled by org and stream
+pub static QUERY_BYTES_SCANNED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "query_bytes_scanned_total",
+        "total bytes scanned by search queries",
+        &["org_id", "stream_name"]
+    )
+    .unwrap()
+});
+
+/// files touched by object-storage search queries, labeled by org and stream
+pub static QUERY_FILES_SCANNED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "query_files_scanned_total",
+        "total files scanned by search queries",
+        &["org_id", "stream_name"]
+    )
+    .unwrap()
+});
+
+/// wall-clock duration of search queries, labeled by org and stream
+pub static QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "query_duration_seconds",
+        "wall-clock duration of search queries",
+        &["org_id", "stream_name"]
+    )
+    .unwrap()
+});
+
+/// a single query's resource consumption, reported once the query finishes
+/// (successfully or not) so failed-but-expensive scans are still metered
+#[derive(Debug, Clone, Default)]
+pub struct QueryUsage {
+    pub org_id: String,
+    pub stream_name: String,
+    pub query: String,
+    pub time_range: (i64, i64),
+    pub bytes_scanned: u64,
+    pub rows_produced: u64,
+    pub file_count: u64,
+    pub duration: std::time::Duration,
+}
+
+/// per-org running totals, aggregated from `QueryUsage` records. This is the
+/// billing/quota-facing counterpart to the Prometheus counters above: it
+/// answers "how much has org X consumed" rather than "what's the current
+/// scrape-able rate", so it's persisted (see `record_query_usage`) instead
+/// of living only in this process's memory.
+#[derive(Debug, Clone, Default)]
+pub struct OrgUsageTotals {
+    pub bytes_scanned: u64,
+    pub rows_produced: u64,
+    pub files_scanned: u64,
+    pub query_count: u64,
+}
+
+/// record a completed query's usage: updates this node's Prometheus
+/// counters, then persists the increment to the shared usage store
+/// (`db::usage`) keyed by org and stream. Persisting matters because
+/// ingestion-style billing data needs to survive a restart and aggregate
+/// across every query node, not just whichever one happened to run this
+/// query -- an in-memory map can do neither. Call this on both the success
+/// and error paths of a search, since a failed scan can still be expensive.
+pub async fn record_query_usage(usage: QueryUsage) {
+    QUERY_BYTES_SCANNED
+        .with_label_values(&[&usage.org_id, &usage.stream_name])
+        .inc_by(usage.bytes_scanned);
+    QUERY_FILES_SCANNED
+        .with_label_values(&[&usage.org_id, &usage.stream_name])
+        .inc_by(usage.file_count);
+    QUERY_DURATION
+        .with_label_values(&[&usage.org_id, &usage.stream_name])
+        .observe(usage.duration.as_secs_f64());
+
+    log::debug!(
+        "usage: org={} stream={} query={:?} range={:?} bytes={} rows={} files={} duration={:?}",
+        usage.org_id,
+        usage.stream_name,
+        usage.query,
+        usage.time_range,
+        usage.bytes_scanned,
+        usage.rows_produced,
+        usage.file_count,
+        usage.duration,
+    );
+
+    if let Err(err) = crate::service::db::usage::increment(&usage).await {
+        log::error!(
+            "usage: failed to persist usage for org={} stream={}: {}",
+            usage.org_id,
+            usage.stream_name,
+            err
+        );
+    }
+}
+
+/// read back an org's accumulated usage totals, summed across every stream,
+/// from the shared usage store. This is the query-side half of the
+/// billing/quota read path the request asked for; putting it behind an HTTP
+/// endpoint is a separate change to the API layer, not this module.
+pub async fn get_org_usage(org_id: &str) -> OrgUsageTotals {
+    crate::service::db::usage::get_org_totals(org_id)
+        .await
+        .unwrap_or_default()
+}
+
+/// same as `get_org_usage` but scoped to one stream, for a per-stream quota
+/// check that shouldn't have to fetch and filter the whole org's usage.
+pub async fn get_stream_usage(org_id: &str, stream_name: &str) -> OrgUsageTotals {
+    crate::service::db::usage::get_stream_totals(org_id, stream_name)
+        .await
+        .unwrap_or_default()
+}